@@ -10,7 +10,10 @@ use std::{
 		ffi::OsStringExt,
 		io::{
 			AsRawHandle,
+			FromRawHandle,
 			IntoRawHandle,
+			OwnedHandle,
+			RawHandle,
 		},
 	},
 	path::Path,
@@ -30,7 +33,6 @@ use windows::{
 	core::PCWSTR,
 	Win32::{
 		Foundation::{
-			CloseHandle,
 			BOOL,
 			HANDLE,
 			HINSTANCE,
@@ -42,13 +44,18 @@ use windows::{
 		System::{
 			DataExchange::{
 				CloseClipboard,
+				EmptyClipboard,
 				GetClipboardData,
 				IsClipboardFormatAvailable,
 				OpenClipboard,
+				SetClipboardData,
 			},
 			Memory::{
+				GlobalAlloc,
+				GlobalFree,
 				GlobalLock,
 				GlobalUnlock,
+				GMEM_MOVEABLE,
 			},
 			ProcessStatus::{
 				K32EnumProcessModulesEx,
@@ -65,40 +72,144 @@ use windows::{
 			},
 		},
 		UI::WindowsAndMessaging::{
+			EM_REPLACESEL,
+			EM_SETSEL,
 			EnumWindows,
 			FindWindowExW,
 			GetWindowThreadProcessId,
 			SendMessageW,
 			SetForegroundWindow,
+			WM_PASTE,
 			WM_SETTEXT,
 		},
 	},
 };
 
-enum Data {
-	Ptr(*const u16),
-	Vec(Vec<u16>),
+// child window classes tried, in order, when the user doesn't pin one down
+// with `--class`
+const DEFAULT_CLASSES: &[&str] = &["Edit", "RichEditD2DPT"];
+
+// where newly sent text lands relative to the target's existing content
+#[derive(Clone, Copy)]
+enum InsertMode {
+	// clobbers the whole document: WM_SETTEXT on an Edit control, select-all
+	// then WM_PASTE on any other class
+	Replace,
+	// EM_SETSEL to the end, then EM_REPLACESEL (WM_PASTE on non-Edit classes)
+	Append,
+	// EM_SETSEL to the start, then EM_REPLACESEL (WM_PASTE on non-Edit classes)
+	Prepend,
+}
+
+struct Args {
+	// executable file name to look for, e.g. "notepad.exe"
+	target: String,
+	// child window class to search for; tries `DEFAULT_CLASSES` when unset
+	class: Option<String>,
+	// command used to spawn `target` when no running instance is found
+	spawn: String,
+	mode: InsertMode,
+	// read stdin onto the clipboard instead of pasting into a target window
+	copy: bool,
+}
+
+impl Args {
+	fn parse() -> Result<Self> {
+		let mut target = None;
+		let mut class = None;
+		let mut spawn = None;
+		let mut mode = InsertMode::Replace;
+		let mut copy = false;
+
+		let mut args = std::env::args().skip(1);
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"--target" => {
+					target = Some(args.next().ok_or_else(|| anyhow!("--target needs a value"))?)
+				}
+				"--class" => {
+					class = Some(args.next().ok_or_else(|| anyhow!("--class needs a value"))?)
+				}
+				"--spawn" => {
+					spawn = Some(args.next().ok_or_else(|| anyhow!("--spawn needs a value"))?)
+				}
+				"--append" => {
+					ensure!(
+						!matches!(mode, InsertMode::Prepend),
+						"--append and --prepend are mutually exclusive"
+					);
+					mode = InsertMode::Append;
+				}
+				"--prepend" => {
+					ensure!(
+						!matches!(mode, InsertMode::Append),
+						"--append and --prepend are mutually exclusive"
+					);
+					mode = InsertMode::Prepend;
+				}
+				"--copy" => copy = true,
+				_ => return Err(anyhow!("unrecognized argument: {arg}")),
+			}
+		}
+
+		let target = target.unwrap_or_else(|| "notepad.exe".to_owned());
+		let spawn = spawn.unwrap_or_else(|| target.clone());
+		Ok(Self {
+			target,
+			class,
+			spawn,
+			mode,
+			copy,
+		})
+	}
+
+	fn classes(&self) -> Vec<&str> {
+		match &self.class {
+			Some(c) => vec![c.as_str()],
+			None => DEFAULT_CLASSES.to_vec(),
+		}
+	}
+}
+
+// an owned process handle; closed automatically on drop via `OwnedHandle`
+struct ProcessHandle(OwnedHandle);
+
+impl ProcessHandle {
+	// takes ownership of a handle obtained from a winapi call such as
+	// `OpenProcess`
+	unsafe fn from_handle(handle: HANDLE) -> Self {
+		Self(OwnedHandle::from_raw_handle(handle.0 as RawHandle))
+	}
+
+	fn as_handle(&self) -> HANDLE {
+		HANDLE(self.0.as_raw_handle() as isize)
+	}
 }
 
-unsafe fn notepad_handle() -> io::Result<(HANDLE, u32)> {
-	match find_notepad()? {
+unsafe fn window_handle(target: &str, spawn: &str) -> io::Result<(ProcessHandle, u32)> {
+	match find_window_by_exe(target)? {
 		Some(x) => Ok(x),
 		None => {
-			let child = Command::new("notepad.exe").spawn()?;
+			let mut parts = spawn.split_whitespace();
+			let program = parts
+				.next()
+				.ok_or_else(|| Error::new(io::ErrorKind::InvalidInput, "empty --spawn command"))?;
+			let child = Command::new(program).args(parts).spawn()?;
 			let pid = child.id();
-			Ok((HANDLE(child.into_raw_handle() as isize), pid))
+			let handle = OwnedHandle::from_raw_handle(child.into_raw_handle());
+			Ok((ProcessHandle(handle), pid))
 		}
 	}
 }
 
 unsafe fn get_hwnd(pid: u32) -> Option<HWND> {
-	static NOTEPAD: AtomicIsize = AtomicIsize::new(0);
+	static TARGET_HWND: AtomicIsize = AtomicIsize::new(0);
 
 	unsafe extern "system" fn callback(hwnd: HWND, pid: LPARAM) -> BOOL {
 		let mut out = 0_u32;
 		GetWindowThreadProcessId(hwnd, &mut out as *mut u32);
 		if out == pid.0 as u32 {
-			NOTEPAD.store(hwnd.0, Ordering::Relaxed);
+			TARGET_HWND.store(hwnd.0, Ordering::Relaxed);
 			false.into()
 		} else {
 			true.into()
@@ -106,9 +217,9 @@ unsafe fn get_hwnd(pid: u32) -> Option<HWND> {
 	}
 
 	EnumWindows(Some(callback), LPARAM(pid as isize));
-	let notepad = NOTEPAD.load(Ordering::Relaxed);
-	if notepad != 0 {
-		Some(HWND(notepad))
+	let hwnd = TARGET_HWND.load(Ordering::Relaxed);
+	if hwnd != 0 {
+		Some(HWND(hwnd))
 	} else {
 		None
 	}
@@ -121,50 +232,178 @@ fn is_stdin_tty() -> bool {
 	unsafe { GetFileType(handle) == FILE_TYPE_CHAR }
 }
 
-fn send_text(notepad_hwnd: HWND, data: &Data) -> Result<isize> {
-	// NOTE: new notepad uses RichEditD2DPT
-	let text = "Edit\0".encode_utf16().collect::<Vec<_>>();
+// length of a null-terminated UTF-16 string, not counting the terminator
+unsafe fn wcslen(mut ptr: *const u16) -> usize {
+	let mut len = 0;
+	while *ptr != 0 {
+		len += 1;
+		ptr = ptr.add(1);
+	}
+	len
+}
+
+// closes the clipboard on drop, keeping `OpenClipboard` balanced on every
+// early return
+struct ClipboardGuard(());
+
+impl ClipboardGuard {
+	unsafe fn open() -> io::Result<Self> {
+		if !OpenClipboard(None).as_bool() {
+			return Err(Error::last_os_error());
+		}
+		Ok(Self(()))
+	}
+}
+
+impl Drop for ClipboardGuard {
+	fn drop(&mut self) {
+		unsafe {
+			CloseClipboard();
+		}
+	}
+}
+
+// copies a null-terminated UTF-16 string onto the clipboard as CF_UNICODETEXT.
+//
+// once `SetClipboardData` succeeds the system owns the global handle, so it
+// must not be freed here; it's only freed on the error paths leading up to
+// that call.
+unsafe fn set_clipboard_text(ptr: *const u16) -> Result<()> {
+	let len_with_nul = wcslen(ptr) + 1;
+	let _clipboard = ClipboardGuard::open()?;
+
+	if !EmptyClipboard().as_bool() {
+		return Err(Error::last_os_error().into());
+	}
+
+	let hmem = GlobalAlloc(GMEM_MOVEABLE, len_with_nul * mem::size_of::<u16>())?;
+
+	let dst = GlobalLock(hmem.0).cast::<u16>();
+	if dst.is_null() {
+		let err = Error::last_os_error();
+		GlobalFree(hmem.0);
+		return Err(err.into());
+	}
+	std::ptr::copy_nonoverlapping(ptr, dst, len_with_nul);
+	GlobalUnlock(hmem.0);
+
+	if SetClipboardData(CF_UNICODETEXT.0, HANDLE(hmem.0)).is_err() {
+		let err = Error::last_os_error();
+		GlobalFree(hmem.0);
+		return Err(err.into());
+	}
+
+	// ownership of `hmem` has transferred to the system; `_clipboard` closes
+	// the clipboard on drop
+	Ok(())
+}
+
+// moves the caret to the start or end of the control's text ahead of an
+// EM_REPLACESEL so the new text lands as an insertion rather than
+// overwriting the current selection
+unsafe fn set_caret(hwnd: HWND, mode: InsertMode) {
+	let (start, end) = match mode {
+		InsertMode::Append => (-1_isize, -1_isize),
+		InsertMode::Prepend => (0, 0),
+		InsertMode::Replace => return,
+	};
+	SendMessageW(hwnd, EM_SETSEL, WPARAM(start as usize), LPARAM(end));
+}
+
+// selects the range a WM_PASTE should land on: the whole document in
+// Replace mode (so the paste overwrites it, matching the Edit/WM_SETTEXT
+// path), otherwise the start/end insertion point
+unsafe fn select_for_paste(hwnd: HWND, mode: InsertMode) {
+	let (start, end) = match mode {
+		InsertMode::Replace => (0_isize, -1_isize),
+		InsertMode::Append => (-1, -1),
+		InsertMode::Prepend => (0, 0),
+	};
+	SendMessageW(hwnd, EM_SETSEL, WPARAM(start as usize), LPARAM(end));
+}
+
+fn send_text(target_hwnd: HWND, data: &[u16], classes: &[&str], mode: InsertMode) -> Result<isize> {
+	let ptr = data.as_ptr();
+
 	unsafe {
-		let hwnd = FindWindowExW(Some(notepad_hwnd), None, Some(PCWSTR(text.as_ptr())), None);
-		ensure!(hwnd.0 != 0, "no edit window found");
+		for &class in classes {
+			let wide = class.encode_utf16().chain([0]).collect::<Vec<_>>();
+			let hwnd = FindWindowExW(Some(target_hwnd), None, Some(PCWSTR(wide.as_ptr())), None);
+			if hwnd.0 == 0 {
+				continue;
+			}
 
-		let ptr = match data {
-			Data::Vec(v) => v.as_ptr(),
-			Data::Ptr(p) => *p,
-		};
+			// a classic Edit control accepts WM_SETTEXT directly; anything
+			// else (e.g. Windows 11 Notepad's RichEditD2DPT) is more
+			// reliably driven by placing the text on the clipboard and
+			// pasting it in
+			if class.eq_ignore_ascii_case("edit") {
+				return Ok(match mode {
+					InsertMode::Replace => {
+						SendMessageW(hwnd, WM_SETTEXT, WPARAM::default(), LPARAM(ptr as isize)).0
+					}
+					InsertMode::Append | InsertMode::Prepend => {
+						set_caret(hwnd, mode);
+						SendMessageW(hwnd, EM_REPLACESEL, WPARAM(true as usize), LPARAM(ptr as isize)).0
+					}
+				});
+			}
+
+			select_for_paste(hwnd, mode);
+			set_clipboard_text(ptr)?;
+			return Ok(SendMessageW(hwnd, WM_PASTE, WPARAM::default(), LPARAM::default()).0);
+		}
 
-		Ok(SendMessageW(hwnd, WM_SETTEXT, WPARAM::default(), LPARAM(ptr as isize)).0)
+		Err(anyhow!(
+			"no matching child window found (tried: {})",
+			classes.join(", ")
+		))
 	}
 }
 
-fn get_text_data() -> io::Result<Data> {
+// reads stdin and places it on the clipboard as CF_UNICODETEXT, the mirror
+// image of the default stdin -> target window flow
+fn copy_mode() -> Result<()> {
+	let mut buf = String::new();
+	io::stdin().lock().read_to_string(&mut buf)?;
+	let mut wide = buf.encode_utf16().collect::<Vec<_>>();
+	wide.push(0);
+	unsafe { set_clipboard_text(wide.as_ptr()) }
+}
+
+fn get_text_data() -> io::Result<Vec<u16>> {
 	if !is_stdin_tty() {
 		let mut buf = String::new();
 		io::stdin().lock().read_to_string(&mut buf)?;
 		buf += "\0";
-		return Ok(Data::Vec(buf.encode_utf16().collect()));
+		return Ok(buf.encode_utf16().collect());
 	}
 
 	unsafe {
 		if !IsClipboardFormatAvailable(CF_UNICODETEXT.0).as_bool() {
-			return Ok(Data::Vec(vec![0]));
-		}
-		if OpenClipboard(None).0 == 0 {
-			return Err(Error::last_os_error());
+			return Ok(vec![0]);
 		}
+
+		// copy the clipboard text into an owned buffer and release the
+		// clipboard immediately: holding it open across the later WM_PASTE
+		// would make the target's own OpenClipboard (needed to read back
+		// CF_UNICODETEXT) fail in its process, silently no-oping the paste
+		let _clipboard = ClipboardGuard::open()?;
 		let handle = GetClipboardData(CF_UNICODETEXT.0)?;
 		if handle.is_invalid() {
 			return Err(Error::last_os_error());
 		}
-		let lock = GlobalLock(handle.0).cast::<u16>();
-		if lock.is_null() {
+		let ptr = GlobalLock(handle.0).cast::<u16>();
+		if ptr.is_null() {
 			return Err(Error::last_os_error());
 		}
-		Ok(Data::Ptr(lock))
+		let text = std::slice::from_raw_parts(ptr, wcslen(ptr) + 1).to_vec();
+		GlobalUnlock(handle.0);
+		Ok(text)
 	}
 }
 
-unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
+unsafe fn find_window_by_exe(exe_name: &str) -> io::Result<Option<(ProcessHandle, u32)>> {
 	let mut pids = vec![0_u32; 1024];
 	let len = pids.len();
 	let mut n_bytes = 0_u32;
@@ -185,7 +424,7 @@ unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
 			BOOL::from(false),
 			pid,
 		) {
-			Ok(x) if !x.is_invalid() => x,
+			Ok(x) if !x.is_invalid() => ProcessHandle::from_handle(x),
 			_ => continue,
 		};
 
@@ -193,7 +432,7 @@ unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
 		let size = mods.len() * mem::size_of::<HINSTANCE>();
 		let mut n_bytes = 0_u32;
 		let res = K32EnumProcessModulesEx(
-			handle,
+			handle.as_handle(),
 			mods.as_ptr() as *mut _,
 			size as _,
 			&mut n_bytes as *mut u32,
@@ -208,7 +447,7 @@ unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
 		}
 
 		let mut buf = vec![0_u16; 1024];
-		let res = K32GetModuleFileNameExW(handle, mods[0], &mut buf);
+		let res = K32GetModuleFileNameExW(handle.as_handle(), mods[0], &mut buf);
 		if res == 0 {
 			return Err(Error::last_os_error());
 		}
@@ -218,11 +457,11 @@ unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
 		let path = Path::new(&path);
 		if path
 			.file_name()
-			.map_or(false, |s| s.eq_ignore_ascii_case("notepad.exe"))
+			.map_or(false, |s| s.eq_ignore_ascii_case(exe_name))
 		{
 			return Ok(Some((handle, pid)));
 		}
-		CloseHandle(handle);
+		// no match: `handle` drops at the end of this iteration, closing it
 	}
 
 	Ok(None)
@@ -230,33 +469,30 @@ unsafe fn find_notepad() -> io::Result<Option<(HANDLE, u32)>> {
 
 fn main() -> Result<()> {
 	unsafe fn run() -> Result<()> {
-		let (handle, pid) = notepad_handle()?;
-		let code = WaitForInputIdle(handle, 2500);
+		let args = Args::parse()?;
+		if args.copy {
+			return copy_mode();
+		}
+
+		let (handle, pid) = window_handle(&args.target, &args.spawn)?;
+		let code = WaitForInputIdle(handle.as_handle(), 2500);
 		ensure!(
 			code == 0,
-			"failed waiting for notepad window: code = {code}"
+			"failed waiting for target window: code = {code}"
 		);
-		let hwnd = get_hwnd(pid).ok_or_else(|| anyhow!("could not locate a notepad window"))?;
+		let hwnd =
+			get_hwnd(pid).ok_or_else(|| anyhow!("could not locate a window for {}", args.target))?;
 
 		ensure!(
 			SetForegroundWindow(hwnd).as_bool(),
-			"failed to focus on notepad"
+			"failed to focus on target window"
 		);
 
 		let data = get_text_data()?;
-		send_text(hwnd, &data)?;
-
-		if let Data::Ptr(p) = data {
-			if !GlobalUnlock(p as isize).as_bool() {
-				return Err(Error::last_os_error().into());
-			}
-
-			if !CloseClipboard().as_bool() {
-				return Err(Error::last_os_error().into());
-			}
-		}
+		send_text(hwnd, &data, &args.classes(), args.mode)?;
 
-		CloseHandle(handle);
+		// `handle` drops here, closing the process handle, even if
+		// `send_text` returned early
 		Ok(())
 	}
 